@@ -0,0 +1,109 @@
+use std::str::FromStr;
+
+use async_stream::try_stream;
+use chrono::NaiveDateTime;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+
+use super::{DbError, Item};
+
+pub async fn connect(database_url: &str) -> anyhow::Result<SqlitePool> {
+    let options = SqliteConnectOptions::from_str(database_url)?
+        .journal_mode(SqliteJournalMode::Wal)
+        .create_if_missing(true);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(50)
+        .connect_with(options)
+        .await?;
+
+    sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+
+    Ok(pool)
+}
+
+pub async fn insert_item(
+    pool: &SqlitePool,
+    title: &str,
+    link: &str,
+    pub_date: NaiveDateTime,
+    description: Option<&str>,
+) -> Result<i64, DbError> {
+    let id = sqlx::query_scalar!(
+        "INSERT INTO items (title, link, pub_date, description) VALUES (?, ?, ?, ?) RETURNING id",
+        title,
+        link,
+        pub_date,
+        description,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn get_item(pool: &SqlitePool, id: i64) -> Result<Option<Item>, DbError> {
+    let item = sqlx::query_as!(
+        Item,
+        r#"
+            SELECT id, title, link, pub_date, description
+            FROM items
+            WHERE id = ?1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(item)
+}
+
+pub async fn recent_items(
+    pool: &SqlitePool,
+    before_id: Option<i64>,
+    limit: i64,
+) -> Result<Vec<Item>, DbError> {
+    let items = sqlx::query_as!(
+        Item,
+        r#"
+            SELECT id, title, link, pub_date, description
+            FROM items
+            WHERE ?1 IS NULL OR id < ?1
+            ORDER BY id DESC
+            LIMIT ?2
+        "#,
+        before_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(items)
+}
+
+pub fn recent_items_stream(
+    pool: SqlitePool,
+    before_id: Option<i64>,
+    limit: i64,
+) -> BoxStream<'static, Result<Item, DbError>> {
+    Box::pin(try_stream! {
+        let mut rows = sqlx::query_as!(
+            Item,
+            r#"
+                SELECT id, title, link, pub_date, description
+                FROM items
+                WHERE ?1 IS NULL OR id < ?1
+                ORDER BY id DESC
+                LIMIT ?2
+            "#,
+            before_id,
+            limit
+        )
+        .fetch(&pool);
+
+        while let Some(item) = rows.try_next().await? {
+            yield item;
+        }
+    })
+}